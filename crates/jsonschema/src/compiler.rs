@@ -0,0 +1,20 @@
+use crate::options::ValidationOptions;
+
+/// Compilation-time context threaded through keyword validators as a schema
+/// is compiled, giving them access to the active [`ValidationOptions`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Context<'a> {
+    options: &'a ValidationOptions,
+}
+
+impl<'a> Context<'a> {
+    /// Start a compilation context for a schema compiled with `options`.
+    pub(crate) fn new(options: &'a ValidationOptions) -> Self {
+        Context { options }
+    }
+
+    /// The options this schema (or subschema) is being compiled with.
+    pub(crate) fn config(&self) -> &'a ValidationOptions {
+        self.options
+    }
+}
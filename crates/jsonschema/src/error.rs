@@ -0,0 +1,48 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::paths::JsonPointer;
+
+/// A single schema validation failure.
+#[derive(Debug)]
+pub struct ValidationError<'a> {
+    /// JSON Pointer to the failing keyword in the schema.
+    pub schema_path: JsonPointer,
+    /// JSON Pointer to the failing value in the instance.
+    pub instance_path: JsonPointer,
+    /// The value that failed validation.
+    pub instance: &'a Value,
+    message: String,
+}
+
+impl fmt::Display for ValidationError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl<'a> ValidationError<'a> {
+    /// `pattern` failed to determine a match because `fancy_regex` hit its
+    /// configured backtracking limit first.
+    ///
+    /// `backtrack_limit` is the limit that was configured for this match (see
+    /// [`ValidationOptions::with_pattern_backtrack_limit`](crate::options::ValidationOptions::with_pattern_backtrack_limit)),
+    /// surfaced here so the message points at the setting to raise.
+    pub(crate) fn backtrack_limit(
+        schema_path: JsonPointer,
+        instance_path: JsonPointer,
+        instance: &'a Value,
+        backtrack_limit: usize,
+        error: fancy_regex::Error,
+    ) -> ValidationError<'a> {
+        ValidationError {
+            schema_path,
+            instance_path,
+            instance,
+            message: format!(
+                "Pattern exceeded the configured backtracking limit of {backtrack_limit}: {error}"
+            ),
+        }
+    }
+}
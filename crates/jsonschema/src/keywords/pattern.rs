@@ -11,17 +11,133 @@ use once_cell::sync::Lazy;
 use serde_json::{Map, Value};
 
 use crate::paths::JsonPointer;
-use std::{collections::VecDeque, ops::Index, sync::Mutex};
+use std::{
+    collections::VecDeque,
+    ops::Index,
+    sync::{Mutex, RwLock},
+};
 
 // Use regex::Regex here to take advantage of replace_all method not available in fancy_regex::Regex
 static CONTROL_GROUPS_RE: Lazy<regex::Regex> =
     Lazy::new(|| regex::Regex::new(r"\\c[A-Za-z]").expect("Is a valid regex"));
 
-static REGEX_CACHE: Lazy<Mutex<LruCache>> = Lazy::new(|| Mutex::new(LruCache::new(10)));
+pub(crate) const DEFAULT_PATTERN_CACHE_CAPACITY: usize = 10;
+// Matches `fancy_regex`'s own default, so leaving the option unset preserves prior behavior.
+pub(crate) const DEFAULT_BACKTRACK_LIMIT: usize = 1_000_000;
+
+// Caps how many distinct `pattern_cache_capacity` values get their own shard. Without
+// this, a caller that varies capacity per call (e.g. derives it per request or tenant)
+// would grow the shard registry itself without bound, even though each shard's own LRU
+// is bounded; the least-recently-created shard is evicted to make room for a new one.
+const MAX_CACHE_SHARDS: usize = 64;
+
+/// Sharded by the configured cache capacity rather than a single global cache: two
+/// validators configured with different capacities (e.g. 1000 vs 5) each get their own
+/// `LruCache` instead of repeatedly resizing (and evicting from) one shared cache. A cache
+/// hit takes only a shared read lock on the registry plus its shard's own mutex; a miss
+/// takes the registry's write lock and holds it while the pattern compiles, so misses are
+/// serialized across all shards.
+struct ShardRegistry {
+    shards: AHashMap<usize, Mutex<LruCache>>,
+    // Tracks creation order so the oldest shard can be evicted once `MAX_CACHE_SHARDS`
+    // distinct capacities have been seen.
+    order: VecDeque<usize>,
+}
+
+impl ShardRegistry {
+    fn new() -> Self {
+        ShardRegistry {
+            shards: AHashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get_or_create(&mut self, capacity: usize) -> &Mutex<LruCache> {
+        if !self.shards.contains_key(&capacity) {
+            if self.shards.len() >= MAX_CACHE_SHARDS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.shards.remove(&oldest);
+                }
+            }
+            self.shards.insert(capacity, Mutex::new(LruCache::new(capacity)));
+            self.order.push_back(capacity);
+        }
+        self.shards
+            .get(&capacity)
+            .expect("just inserted above, or already present")
+    }
+}
+
+static REGEX_CACHES: Lazy<RwLock<ShardRegistry>> = Lazy::new(|| RwLock::new(ShardRegistry::new()));
+
+/// Look up `key` in the shard for `capacity`, compiling and inserting it on a miss.
+fn cached_compile(
+    capacity: usize,
+    key: &CacheKey,
+    engine: PatternEngine,
+    backtrack_limit: usize,
+    unicode: bool,
+) -> Result<CompiledPattern, ()> {
+    {
+        let registry = REGEX_CACHES.read().expect("Lock is poisoned");
+        if let Some(shard) = registry.shards.get(&capacity) {
+            if let Some(compiled) = shard.lock().expect("Lock is poisoned").get(key) {
+                return Ok(compiled.clone());
+            }
+        }
+    }
+    let mut registry = REGEX_CACHES.write().expect("Lock is poisoned");
+    let shard = registry.get_or_create(capacity);
+    let mut cache = shard.lock().expect("Lock is poisoned");
+    if let Some(compiled) = cache.get(key) {
+        return Ok(compiled.clone());
+    }
+    let compiled = compile_pattern(&key.0, engine, backtrack_limit, unicode)?;
+    cache.insert(key.clone(), compiled.clone());
+    Ok(compiled)
+}
+
+/// Regex backend used to compile `pattern` schemas.
+///
+/// `fancy_regex` supports ECMA-262 features like backreferences and
+/// lookaround that `regex` does not, but `regex` is significantly faster and
+/// does not share `fancy_regex`'s global backtracking cache. [`PatternEngine::Auto`]
+/// picks the faster engine whenever a pattern doesn't need the features only
+/// `fancy_regex` provides.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternEngine {
+    /// Use `regex::Regex` unless the pattern requires backreferences or
+    /// lookaround, in which case fall back to `fancy_regex::Regex`.
+    #[default]
+    Auto,
+    /// Always compile with `fancy_regex::Regex`.
+    Fancy,
+    /// Always compile with `regex::Regex`. Patterns that require
+    /// backreferences or lookaround will fail to compile.
+    Regex,
+}
+
+type CacheKey = (String, PatternEngine, usize, bool);
+
+#[derive(Debug, Clone)]
+enum CompiledPattern {
+    Fast(regex::Regex),
+    Fancy(fancy_regex::Regex),
+}
+
+impl CompiledPattern {
+    #[allow(clippy::result_large_err)]
+    fn is_match(&self, text: &str) -> Result<bool, fancy_regex::Error> {
+        match self {
+            CompiledPattern::Fast(re) => Ok(re.is_match(text)),
+            CompiledPattern::Fancy(re) => re.is_match(text),
+        }
+    }
+}
 
 struct LruCache {
-    map: AHashMap<String, fancy_regex::Regex>,
-    queue: VecDeque<String>,
+    map: AHashMap<CacheKey, CompiledPattern>,
+    queue: VecDeque<CacheKey>,
     capacity: usize,
 }
 
@@ -30,11 +146,11 @@ impl LruCache {
         LruCache {
             map: AHashMap::new(),
             queue: VecDeque::new(),
-            capacity,
+            capacity: capacity.max(1),
         }
     }
 
-    fn get(&mut self, key: &str) -> Option<&fancy_regex::Regex> {
+    fn get(&mut self, key: &CacheKey) -> Option<&CompiledPattern> {
         if let Some(value) = self.map.get(key) {
             let index = self.queue.iter().position(|x| x == key).unwrap();
             let k = self.queue.remove(index).unwrap();
@@ -45,7 +161,7 @@ impl LruCache {
         }
     }
 
-    fn insert(&mut self, key: String, value: fancy_regex::Regex) -> Option<fancy_regex::Regex> {
+    fn insert(&mut self, key: CacheKey, value: CompiledPattern) -> Option<CompiledPattern> {
         if self.map.len() >= self.capacity && !self.map.contains_key(&key) {
             if let Some(lru_key) = self.queue.pop_front() {
                 self.map.remove(&lru_key);
@@ -64,8 +180,9 @@ impl LruCache {
 
 pub(crate) struct PatternValidator {
     original: String,
-    pattern: fancy_regex::Regex,
+    pattern: CompiledPattern,
     schema_path: JsonPointer,
+    backtrack_limit: usize,
 }
 
 impl PatternValidator {
@@ -76,28 +193,29 @@ impl PatternValidator {
     ) -> CompilationResult<'a> {
         match pattern {
             Value::String(item) => {
-                let mut cache = REGEX_CACHE.lock().expect("Lock is poisoned");
-                let pattern = if let Some(regex) = cache.get(item) {
-                    regex.clone()
-                } else {
-                    let regex = match convert_regex(item) {
-                        Ok(r) => r,
-                        Err(_) => {
-                            return Err(ValidationError::format(
-                                JsonPointer::default(),
-                                ctx.clone().into_pointer(),
-                                pattern,
-                                "regex",
-                            ))
-                        }
-                    };
-                    cache.insert(item.clone(), regex.clone());
-                    regex
+                let engine = ctx.config().pattern_engine();
+                let backtrack_limit = ctx.config().pattern_backtrack_limit();
+                let unicode = ctx.config().unicode_regex();
+                let capacity = ctx.config().pattern_cache_capacity();
+                let key = (item.clone(), engine, backtrack_limit, unicode);
+
+                let pattern = match cached_compile(capacity, &key, engine, backtrack_limit, unicode)
+                {
+                    Ok(compiled) => compiled,
+                    Err(()) => {
+                        return Err(ValidationError::format(
+                            JsonPointer::default(),
+                            ctx.clone().into_pointer(),
+                            pattern,
+                            "regex",
+                        ))
+                    }
                 };
                 Ok(Box::new(PatternValidator {
                     original: item.clone(),
                     pattern,
                     schema_path: ctx.as_pointer_with("pattern"),
+                    backtrack_limit,
                 }))
             }
             _ => Err(ValidationError::single_type_error(
@@ -133,6 +251,7 @@ impl Validate for PatternValidator {
                         self.schema_path.clone(),
                         instance_path.into(),
                         instance,
+                        self.backtrack_limit,
                         e,
                     ));
                 }
@@ -149,48 +268,193 @@ impl Validate for PatternValidator {
     }
 }
 
+/// Compile `pattern` (already in its raw, schema-authored form) into a
+/// [`CompiledPattern`], selecting the regex backend according to `engine`,
+/// capping `fancy_regex` backtracking at `backtrack_limit`, and expanding
+/// `\d`/`\w`/`\s` as Unicode-aware classes when `unicode` is set (see
+/// [`translate_pattern`]).
+fn compile_pattern(
+    pattern: &str,
+    engine: PatternEngine,
+    backtrack_limit: usize,
+    unicode: bool,
+) -> Result<CompiledPattern, ()> {
+    let translated = translate_pattern(pattern, unicode);
+    let compile_fancy = |source: &str| {
+        fancy_regex::RegexBuilder::new(source)
+            .backtrack_limit(backtrack_limit)
+            .build()
+            .map(CompiledPattern::Fancy)
+            .map_err(|_| ())
+    };
+    match engine {
+        PatternEngine::Fancy => compile_fancy(&translated),
+        PatternEngine::Regex => regex::Regex::new(&translated)
+            .map(CompiledPattern::Fast)
+            .map_err(|_| ()),
+        PatternEngine::Auto => {
+            if needs_fancy_features(pattern) {
+                compile_fancy(&translated)
+            } else {
+                regex::Regex::new(&translated)
+                    .map(CompiledPattern::Fast)
+                    .or_else(|_| compile_fancy(&translated))
+            }
+        }
+    }
+}
+
+/// Whether `pattern` uses ECMA-262 features (backreferences, lookaround)
+/// that only `fancy_regex` supports.
+fn needs_fancy_features(pattern: &str) -> bool {
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let is_backreference = matches!(
+                    chars.peek(),
+                    Some(&(_, next)) if next.is_ascii_digit() || next == 'k'
+                );
+                if is_backreference {
+                    return true;
+                }
+                chars.next();
+            }
+            '(' if pattern[i..].starts_with("(?=")
+                || pattern[i..].starts_with("(?!")
+                || pattern[i..].starts_with("(?<=")
+                || pattern[i..].starts_with("(?<!") =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
 // ECMA 262 has differences
 #[allow(clippy::result_large_err)]
 pub(crate) fn convert_regex(pattern: &str) -> Result<fancy_regex::Regex, fancy_regex::Error> {
+    fancy_regex::Regex::new(&translate_pattern(pattern, false))
+}
+
+const ASCII_WHITESPACE: &str = " \t\n\r\u{000b}\u{000c}\u{2003}\u{feff}\u{2029}\u{00a0}";
+
+/// Rewrite ECMA-262-only escapes (`\d`, `\w`, `\s` and their negations,
+/// `\cX` control groups) into their Rust regex equivalents.
+///
+/// This is a small state machine that tracks whether it is currently inside
+/// a `[...]` character class, because the substitution differs depending on
+/// context: outside a class `\d` must become the bracketed `[0-9]`, but
+/// inside a class (e.g. `[\w.-]`) only the *contents* (`0-9`) may be
+/// substituted, since nesting brackets there would change the meaning of the
+/// pattern (or fail to parse).
+///
+/// ECMA-262 treats `\d`/`\w`/`\s` as ASCII-only unless the pattern's `u`
+/// (Unicode) flag is set, in which case they widen to their Unicode
+/// categories. When `unicode` is `true` these escapes (and their negations)
+/// are passed through unchanged instead of expanded to ASCII bracket sets,
+/// since both `regex` and `fancy_regex` already treat the bare escapes as
+/// Unicode-aware. Unicode property escapes like `\p{L}` are always passed
+/// through unchanged, in either mode, since neither engine needs rewriting
+/// for those.
+fn translate_pattern(pattern: &str, unicode: bool) -> String {
     // replace control chars
     let new_pattern = CONTROL_GROUPS_RE.replace_all(pattern, replace_control_group);
     let mut out = String::with_capacity(new_pattern.len());
     let mut chars = new_pattern.chars().peekable();
+    let mut in_class = false;
     // To convert character group we need to iterate over chars and in case of `\` take a look
     // at the next char to detect whether this group should be converted
     while let Some(current) = chars.next() {
-        if current == '\\' {
-            // Possible character group
-            if let Some(next) = chars.next() {
-                match next {
-                    'd' => out.push_str("[0-9]"),
-                    'D' => out.push_str("[^0-9]"),
-                    'w' => out.push_str("[A-Za-z0-9_]"),
-                    'W' => out.push_str("[^A-Za-z0-9_]"),
-                    's' => {
-                        out.push_str("[ \t\n\r\u{000b}\u{000c}\u{2003}\u{feff}\u{2029}\u{00a0}]")
-                    }
-                    'S' => {
-                        out.push_str("[^ \t\n\r\u{000b}\u{000c}\u{2003}\u{feff}\u{2029}\u{00a0}]")
-                    }
-                    _ => {
-                        // Nothing interesting, push as is
-                        out.push(current);
-                        out.push(next)
+        match current {
+            '\\' => {
+                // Possible character group
+                if let Some(next) = chars.next() {
+                    match next {
+                        'd' | 'D' | 'w' | 'W' | 's' | 'S' if unicode => {
+                            // Native escapes are already Unicode-aware in both engines.
+                            out.push('\\');
+                            out.push(next);
+                        }
+                        'd' => out.push_str(if in_class { "0-9" } else { "[0-9]" }),
+                        'w' => out.push_str(if in_class {
+                            "A-Za-z0-9_"
+                        } else {
+                            "[A-Za-z0-9_]"
+                        }),
+                        's' => {
+                            if in_class {
+                                out.push_str(ASCII_WHITESPACE);
+                            } else {
+                                out.push('[');
+                                out.push_str(ASCII_WHITESPACE);
+                                out.push(']');
+                            }
+                        }
+                        // Negating a class from within a class would require set difference,
+                        // which isn't representable as a simple substitution. Pass the escape
+                        // through unchanged inside a class as a documented limitation; it keeps
+                        // its native (Unicode-based) meaning there instead of the ASCII one.
+                        'D' => {
+                            if in_class {
+                                out.push('\\');
+                                out.push('D');
+                            } else {
+                                out.push_str("[^0-9]");
+                            }
+                        }
+                        'W' => {
+                            if in_class {
+                                out.push('\\');
+                                out.push('W');
+                            } else {
+                                out.push_str("[^A-Za-z0-9_]");
+                            }
+                        }
+                        'S' => {
+                            if in_class {
+                                out.push('\\');
+                                out.push('S');
+                            } else {
+                                out.push('[');
+                                out.push('^');
+                                out.push_str(ASCII_WHITESPACE);
+                                out.push(']');
+                            }
+                        }
+                        '[' | ']' => {
+                            // Escaped brackets are literals and must not toggle class state
+                            out.push('\\');
+                            out.push(next);
+                        }
+                        _ => {
+                            // Nothing interesting, push as is. This also covers Unicode property
+                            // escapes like `\p{L}`, which `fancy_regex` understands natively.
+                            out.push(current);
+                            out.push(next)
+                        }
                     }
+                } else {
+                    // End of the string, push the last char.
+                    // Note that it is an incomplete escape sequence and will lead to an error on
+                    // the next step
+                    out.push(current);
                 }
-            } else {
-                // End of the string, push the last char.
-                // Note that it is an incomplete escape sequence and will lead to an error on
-                // the next step
-                out.push(current);
             }
-        } else {
-            // Regular character
-            out.push(current);
+            '[' if !in_class => {
+                in_class = true;
+                out.push('[');
+            }
+            ']' if in_class => {
+                in_class = false;
+                out.push(']');
+            }
+            _ => out.push(current),
         }
     }
-    fancy_regex::Regex::new(&out)
+    out
 }
 
 #[allow(clippy::arithmetic_side_effects)]
@@ -219,7 +483,7 @@ pub(crate) fn compile<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests_util;
+    use crate::{compiler, options::ValidationOptions, tests_util};
     use serde_json::json;
     use test_case::test_case;
 
@@ -227,6 +491,12 @@ mod tests {
     #[test_case(r"^[\w\-\.\+]+$", "CC-BY-!", false)]
     #[test_case(r"^\W+$", "1_0", false)]
     #[test_case(r"\\w", r"\w", true)]
+    #[test_case(r"[\w.-]", "a", true)]
+    #[test_case(r"[\w.-]", "!", false)]
+    #[test_case(r"^[^\d]+$", "abc", true)]
+    #[test_case(r"^[^\d]+$", "a1c", false)]
+    #[test_case(r"^\d+$", "123", true)]
+    #[test_case(r"^\d+$", "12a", false)]
     fn regex_matches(pattern: &str, text: &str, is_matching: bool) {
         let validator = convert_regex(pattern).expect("A valid regex");
         assert_eq!(
@@ -250,8 +520,155 @@ mod tests {
         assert_eq!(validator.is_valid(&text), is_matching)
     }
 
+    #[test_case(r"[\w.-]", "[A-Za-z0-9_.-]"; "word class inside a class")]
+    #[test_case(r"[^\d]", "[^0-9]"; "digit class inside a negated class")]
+    #[test_case(r"\d", "[0-9]"; "digit class outside a class")]
+    #[test_case(r"\[\d\]", r"\[[0-9]\]"; "escaped brackets do not toggle class state")]
+    fn translate_pattern_cases(pattern: &str, expected: &str) {
+        assert_eq!(translate_pattern(pattern, false), expected);
+    }
+
     #[test]
     fn schema_path() {
         tests_util::assert_schema_path(&json!({"pattern": "^f"}), &json!("b"), "/pattern")
     }
+
+    #[test_case("^[a-z]+$", PatternEngine::Regex)]
+    #[test_case("^(?!eo:)", PatternEngine::Fancy)]
+    #[test_case("^(?!eo:)", PatternEngine::Auto)]
+    fn engine_selection_compiles(pattern: &str, engine: PatternEngine) {
+        assert!(compile_pattern(pattern, engine, DEFAULT_BACKTRACK_LIMIT, false).is_ok());
+    }
+
+    #[test]
+    fn regex_engine_rejects_lookaround() {
+        assert!(compile_pattern(
+            "^(?!eo:)",
+            PatternEngine::Regex,
+            DEFAULT_BACKTRACK_LIMIT,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cache_is_sharded_by_capacity() {
+        // Two distinct capacities; other tests may already have created a shard for
+        // either one, and the assertions below hold whether or not they have.
+        let key = (
+            "^cache-shard-test$".to_string(),
+            PatternEngine::Regex,
+            DEFAULT_BACKTRACK_LIMIT,
+            false,
+        );
+        for capacity in [930_001, 1] {
+            let compiled = cached_compile(
+                capacity,
+                &key,
+                PatternEngine::Regex,
+                DEFAULT_BACKTRACK_LIMIT,
+                false,
+            )
+            .expect("compiles");
+            assert!(compiled.is_match("cache-shard-test").expect("matches"));
+            // A cache hit on the second lookup must behave identically to the first.
+            let cached = cached_compile(
+                capacity,
+                &key,
+                PatternEngine::Regex,
+                DEFAULT_BACKTRACK_LIMIT,
+                false,
+            )
+            .expect("compiles");
+            assert!(cached.is_match("cache-shard-test").expect("matches"));
+            // Each capacity has its own shard, sized to that capacity.
+            let registry = REGEX_CACHES.read().expect("Lock is poisoned");
+            let shard = registry.shards.get(&capacity).expect("a shard for this capacity");
+            assert_eq!(shard.lock().expect("Lock is poisoned").capacity, capacity);
+        }
+    }
+
+    #[test]
+    fn options_plumb_through_to_pattern_validator() {
+        // Exercises the actual `ValidationOptions` -> `compiler::Context` ->
+        // `PatternValidator::compile` path, rather than calling `compile_pattern`
+        // directly with hand-picked parameters.
+        let mut options = ValidationOptions::default();
+        options
+            .with_pattern_cache_capacity(1)
+            .with_pattern_engine(PatternEngine::Fancy)
+            .with_unicode_regex(true)
+            .with_pattern_backtrack_limit(DEFAULT_BACKTRACK_LIMIT);
+        let ctx = compiler::Context::new(&options);
+
+        let validator = PatternValidator::compile(&ctx, &json!(r"^\w+$"))
+            .expect("compiles through the configured options");
+        // `unicode_regex` is on, so `\w` matches non-ASCII letters.
+        assert!(validator.is_valid(&json!("héllo")));
+        assert!(!validator.is_valid(&json!("hello!")));
+    }
+
+    #[test]
+    fn backtrack_limit_is_applied() {
+        let limit = 1;
+        // The lookahead makes `fancy_regex` run this pattern on its own backtracking
+        // engine (a pattern without such features is delegated to `regex` and never
+        // counts backtracks), and the alternation backtracks heavily on this input
+        // before the lookahead can fail, which the configured limit caps.
+        let compiled = compile_pattern("(?i)(a|b|ab)*(?=c)", PatternEngine::Fancy, limit, false)
+            .expect("compiles fine; the limit only applies at match time");
+        let catastrophic_input = "ab".repeat(28);
+        let fancy_error = compiled
+            .is_match(&catastrophic_input)
+            .expect_err("the tiny backtrack limit should be hit before a verdict is reached");
+
+        let instance = json!(catastrophic_input);
+        let error = ValidationError::backtrack_limit(
+            JsonPointer::default(),
+            JsonPointer::default(),
+            &instance,
+            limit,
+            fancy_error,
+        );
+        assert!(error.to_string().contains(&limit.to_string()));
+    }
+
+    #[test_case(r"\p{L}+", "café", true)]
+    #[test_case(r"\p{Nd}+", "١٢٣", true)]
+    #[test_case(r"\p{Nd}+", "abc", false)]
+    fn unicode_property_escapes(pattern: &str, text: &str, is_matching: bool) {
+        let compiled =
+            compile_pattern(pattern, PatternEngine::Fancy, DEFAULT_BACKTRACK_LIMIT, true)
+                .expect("A valid regex");
+        assert_eq!(
+            compiled.is_match(text).expect("A valid pattern"),
+            is_matching
+        );
+    }
+
+    #[test_case(r"^\w+$", "héllo", true)]
+    #[test_case(r"^\w+$", "hello!", false)]
+    fn unicode_word_class(pattern: &str, text: &str, is_matching: bool) {
+        let compiled =
+            compile_pattern(pattern, PatternEngine::Fancy, DEFAULT_BACKTRACK_LIMIT, true)
+                .expect("A valid regex");
+        assert_eq!(
+            compiled.is_match(text).expect("A valid pattern"),
+            is_matching
+        );
+    }
+
+    #[test]
+    fn ascii_mode_is_still_default() {
+        // Without the Unicode option, \w stays ASCII-only for backward compatibility.
+        assert!(!ValidationOptions::default().unicode_regex());
+        let compiled = compile_pattern(
+            r"^\w+$",
+            PatternEngine::Fancy,
+            DEFAULT_BACKTRACK_LIMIT,
+            false,
+        )
+        .expect("A valid regex");
+        assert!(!compiled.is_match("héllo").expect("A valid pattern"));
+    }
 }
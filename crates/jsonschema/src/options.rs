@@ -0,0 +1,74 @@
+use crate::keywords::pattern::{PatternEngine, DEFAULT_BACKTRACK_LIMIT, DEFAULT_PATTERN_CACHE_CAPACITY};
+
+/// Compiler-wide configuration that controls how schemas are compiled.
+///
+/// Built with the `with_*` setters and passed to the compiler; individual
+/// keyword validators read back the settings relevant to them through
+/// [`compiler::Context::config`](crate::compiler::Context::config).
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    pattern_engine: PatternEngine,
+    pattern_cache_capacity: usize,
+    pattern_backtrack_limit: usize,
+    unicode_regex: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            pattern_engine: PatternEngine::default(),
+            pattern_cache_capacity: DEFAULT_PATTERN_CACHE_CAPACITY,
+            pattern_backtrack_limit: DEFAULT_BACKTRACK_LIMIT,
+            unicode_regex: false,
+        }
+    }
+}
+
+impl ValidationOptions {
+    /// Select the regex backend used to compile `pattern` schemas.
+    pub fn with_pattern_engine(&mut self, engine: PatternEngine) -> &mut Self {
+        self.pattern_engine = engine;
+        self
+    }
+
+    /// Set how many distinct compiled patterns are kept in the pattern cache.
+    /// A capacity of 0 is treated as 1.
+    pub fn with_pattern_cache_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.pattern_cache_capacity = capacity;
+        self
+    }
+
+    pub(crate) fn pattern_engine(&self) -> PatternEngine {
+        self.pattern_engine
+    }
+
+    /// Set `fancy_regex`'s backtracking cap for `pattern` schemas.
+    ///
+    /// Lower it to defend against pathological patterns when compiling
+    /// untrusted schemas, or raise it for trusted schemas whose patterns
+    /// need more backtracking budget to match correctly.
+    pub fn with_pattern_backtrack_limit(&mut self, limit: usize) -> &mut Self {
+        self.pattern_backtrack_limit = limit;
+        self
+    }
+
+    pub(crate) fn pattern_cache_capacity(&self) -> usize {
+        self.pattern_cache_capacity
+    }
+
+    /// Make `\d`, `\w`, `\s` (and their negations) in `pattern` schemas
+    /// Unicode-aware instead of ASCII-only. Unicode property escapes like
+    /// `\p{L}` are passed through whether or not this is set.
+    pub fn with_unicode_regex(&mut self, enabled: bool) -> &mut Self {
+        self.unicode_regex = enabled;
+        self
+    }
+
+    pub(crate) fn pattern_backtrack_limit(&self) -> usize {
+        self.pattern_backtrack_limit
+    }
+
+    pub(crate) fn unicode_regex(&self) -> bool {
+        self.unicode_regex
+    }
+}